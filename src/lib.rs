@@ -0,0 +1,801 @@
+//! The brainfuck parser, optimizer, and interpreter core. With the default `std` feature turned
+//! off this crate is `#![no_std]`, so it can be embedded in firmware or WASM where
+//! `stdin`/`stdout`/`File` don't exist; `std` is on by default for the CLI front end in `main.rs`.
+//! With `std`, `execute` and `Debugger` take `std::io`'s `Read`/`Write`; without it, they take the
+//! minimal `Read`/`Write` traits of the `no_std_io` module below.
+#![cfg_attr(not(feature = "std"), no_std)]
+
+#[cfg(not(feature = "std"))]
+#[macro_use]
+extern crate alloc;
+
+// A minimal stand-in for `std::io`'s `Read`/`Write`/`Result`, just enough for the byte-at-a-time
+// I/O `step_op` does. The crate used to pull in the external `core_io` crate for this, but its
+// published build script doesn't support current rustc, so it can never actually be built; a
+// two-method trait is all this interpreter needs anyway.
+#[cfg(not(feature = "std"))]
+pub mod no_std_io {
+    pub struct Error;
+    pub type Result<T> = core::result::Result<T, Error>;
+
+    pub trait Read {
+        fn read(&mut self, buf: &mut [u8]) -> Result<usize>;
+    }
+
+    pub trait Write {
+        fn write(&mut self, buf: &[u8]) -> Result<usize>;
+    }
+}
+
+#[cfg(feature = "std")]
+use std::io::{self, Read, Write};
+#[cfg(not(feature = "std"))]
+use no_std_io as io;
+#[cfg(not(feature = "std"))]
+use no_std_io::{Read, Write};
+
+#[cfg(feature = "std")]
+use std::vec::Vec;
+#[cfg(not(feature = "std"))]
+use alloc::vec::Vec;
+#[cfg(not(feature = "std"))]
+use alloc::boxed::Box;
+#[cfg(not(feature = "std"))]
+use alloc::string::String;
+
+#[cfg(feature = "std")]
+use std::iter::repeat;
+#[cfg(not(feature = "std"))]
+use core::iter::repeat;
+
+#[cfg(feature = "std")]
+use std::marker::PhantomData;
+#[cfg(not(feature = "std"))]
+use core::marker::PhantomData;
+
+// A brainfuck instruction.
+#[derive(Clone, Copy, Debug)]
+pub enum Op {
+    Inc,   // +
+    Dec,   // -
+    Left,  // <
+    Right, // >
+    Read,  // ,
+    Write, // .
+
+    // Each loop instruction stores the index of its matching loop instruction.
+    LoopStart(usize), // [
+    LoopEnd(usize),   // ]
+
+    // The instructions below don't come out of `parse`; `optimize` introduces them by fusing
+    // runs of the instructions above.
+    Add(isize),  // a run of Inc/Dec folded into one net change to the current cell
+    Move(isize), // a run of Left/Right folded into one net change to the head position
+    SetZero,    // the `[-]` / `[+]` idiom: zero the current cell without looping
+}
+
+// Parse errors contain the index of the offending character in the original program source.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ParseError { UnmatchedLoopStart(usize), UnmatchedLoopEnd(usize) }
+
+// A parsed (and, after `optimize`, fused) program, together with the source character index
+// that produced each `Op`. `src[i]` is the character index that `ops[i]` came from; `optimize`
+// keeps the two in lockstep so breakpoints and the disassembler can still refer back to source
+// positions after fusion collapses several characters into one instruction.
+pub struct Program {
+    pub ops: Vec<Op>,
+    pub src: Vec<usize>,
+}
+
+pub fn parse(program: &str) -> Result<Program, ParseError> {
+    let mut ops = Vec::new();
+    let mut src = Vec::new();
+    let mut loop_stack = Vec::new();
+
+    for (i, op) in program.chars().enumerate() {
+        match op {
+            '+' => { ops.push(Op::Inc); src.push(i); },
+            '-' => { ops.push(Op::Dec); src.push(i); },
+            '<' => { ops.push(Op::Left); src.push(i); },
+            '>' => { ops.push(Op::Right); src.push(i); },
+            ',' => { ops.push(Op::Read); src.push(i); },
+            '.' => { ops.push(Op::Write); src.push(i); },
+            '[' => {
+                loop_stack.push(ops.len());
+                ops.push(Op::LoopStart(0));
+                src.push(i);
+            },
+            ']' => match loop_stack.pop() {
+                Some(loop_start) => {
+                    ops[loop_start] = Op::LoopStart(ops.len());
+                    ops.push(Op::LoopEnd(loop_start));
+                    src.push(i);
+                },
+                None => return Err(ParseError::UnmatchedLoopEnd(i)),
+            },
+            _   => {}
+        }
+    }
+
+    if loop_stack.is_empty() {
+        Ok(Program { ops: ops, src: src })
+    } else {
+        Err(ParseError::UnmatchedLoopStart(src[loop_stack[0]]))
+    }
+}
+
+// Rewrites a freshly parsed program into a denser IR: runs of `Inc`/`Dec` and `Left`/`Right`
+// collapse into single `Add`/`Move` instructions, and the `[-]`/`[+]` clear-cell idiom
+// collapses into `SetZero`. Collapsing instructions shifts indices around, so loop targets are
+// recomputed from scratch at the end using the same stack algorithm `parse` uses.
+pub fn optimize(program: Program) -> Program {
+    let mut program = fuse_runs(program);
+    fuse_set_zero(&mut program);
+    relink_loops(&mut program);
+    program
+}
+
+// Folds consecutive `Inc`/`Dec` into a single `Add`, and consecutive `Left`/`Right` into a
+// single `Move`. Loop instructions are passed through with their targets reset to 0, since
+// fusing instructions invalidates every jump target; `relink_loops` fixes them up afterward.
+// Each fused instruction keeps the source index of the first character in the run it replaces.
+fn fuse_runs(program: Program) -> Program {
+    let Program { ops, src } = program;
+    let mut fused_ops = Vec::with_capacity(ops.len());
+    let mut fused_src = Vec::with_capacity(ops.len());
+    let mut i = 0;
+
+    while i < ops.len() {
+        match ops[i] {
+            Op::Inc | Op::Dec => {
+                let start = i;
+                let mut net: isize = 0;
+                while i < ops.len() {
+                    match ops[i] {
+                        Op::Inc => { net += 1; i += 1; },
+                        Op::Dec => { net -= 1; i += 1; },
+                        _       => break,
+                    }
+                }
+                fused_ops.push(Op::Add(net));
+                fused_src.push(src[start]);
+            },
+            Op::Left | Op::Right => {
+                let start = i;
+                let mut net: isize = 0;
+                while i < ops.len() {
+                    match ops[i] {
+                        Op::Left  => { net -= 1; i += 1; },
+                        Op::Right => { net += 1; i += 1; },
+                        _         => break,
+                    }
+                }
+                fused_ops.push(Op::Move(net));
+                fused_src.push(src[start]);
+            },
+            Op::Read         => { fused_ops.push(Op::Read); fused_src.push(src[i]); i += 1; },
+            Op::Write        => { fused_ops.push(Op::Write); fused_src.push(src[i]); i += 1; },
+            Op::LoopStart(_) => { fused_ops.push(Op::LoopStart(0)); fused_src.push(src[i]); i += 1; },
+            Op::LoopEnd(_)   => { fused_ops.push(Op::LoopEnd(0)); fused_src.push(src[i]); i += 1; },
+            Op::Add(_) | Op::Move(_) | Op::SetZero =>
+                panic!("fuse_runs called on already-optimized ops"),
+        }
+    }
+
+    Program { ops: fused_ops, src: fused_src }
+}
+
+// Recognizes `LoopStart`/`Add(1 or -1)`/`LoopEnd` windows -- the `[-]`/`[+]` idiom -- and
+// replaces them with a single `SetZero`, keeping the source index of the `LoopStart`.
+fn fuse_set_zero(program: &mut Program) {
+    let mut result_ops = Vec::with_capacity(program.ops.len());
+    let mut result_src = Vec::with_capacity(program.ops.len());
+    let mut i = 0;
+
+    while i < program.ops.len() {
+        let window = if i + 2 < program.ops.len() {
+            Some((program.ops[i], program.ops[i + 1], program.ops[i + 2]))
+        } else {
+            None
+        };
+
+        match window {
+            Some((Op::LoopStart(_), Op::Add(d), Op::LoopEnd(_))) if d == 1 || d == -1 => {
+                result_ops.push(Op::SetZero);
+                result_src.push(program.src[i]);
+                i += 3;
+            },
+            _ => {
+                result_ops.push(program.ops[i]);
+                result_src.push(program.src[i]);
+                i += 1;
+            },
+        }
+    }
+
+    program.ops = result_ops;
+    program.src = result_src;
+}
+
+// Recomputes every `LoopStart`/`LoopEnd` target from scratch, using the same loop-stack
+// algorithm `parse` uses. Needed after any pass that changes the instruction count. Doesn't
+// touch `src`, since this pass only rewrites jump targets in place.
+fn relink_loops(program: &mut Program) {
+    let ops = &mut program.ops;
+    let mut loop_stack = Vec::new();
+
+    for i in 0..ops.len() {
+        match ops[i] {
+            Op::LoopStart(_) => loop_stack.push(i),
+            Op::LoopEnd(_) => {
+                let start = loop_stack.pop().expect("unmatched loop end survived parsing");
+                ops[start] = Op::LoopStart(i);
+                ops[i] = Op::LoopEnd(start);
+            },
+            _ => {},
+        }
+    }
+}
+
+// Renders the fused IR back to human-readable form, e.g. `"Add(3) @ src:42"`, one line per
+// instruction, so a user can see what the optimizer produced from their source.
+pub fn disassemble(program: &Program) -> Vec<String> {
+    program.ops.iter().zip(program.src.iter())
+        .map(|(op, src)| format!("{:?} @ src:{}", op, src))
+        .collect()
+}
+
+// A value that can live in a tape cell. The cell width (`u8`/`u16`/`u32`) is the tape's type
+// parameter; `execute` does not read `Config::cell_width`, which a front end uses to pick the
+// tape type at runtime (see `run` in `main.rs`). Arithmetic always wraps (`Wrapping<u8>`-style semantics) instead of
+// panicking on overflow/underflow, matching what most BF programs assume.
+pub trait Cell: Copy + PartialEq {
+    fn zero() -> Self;
+    fn one() -> Self;
+    fn max_value() -> Self;
+    fn wrapping_add(self, other: Self) -> Self;
+    fn wrapping_sub(self, other: Self) -> Self;
+    fn from_byte(byte: u8) -> Self;
+    fn to_byte(self) -> u8;
+}
+
+impl Cell for u8 {
+    fn zero() -> u8 { 0 }
+    fn one() -> u8 { 1 }
+    fn max_value() -> u8 { u8::max_value() }
+    fn wrapping_add(self, other: u8) -> u8 { u8::wrapping_add(self, other) }
+    fn wrapping_sub(self, other: u8) -> u8 { u8::wrapping_sub(self, other) }
+    fn from_byte(byte: u8) -> u8 { byte }
+    fn to_byte(self) -> u8 { self }
+}
+
+impl Cell for u16 {
+    fn zero() -> u16 { 0 }
+    fn one() -> u16 { 1 }
+    fn max_value() -> u16 { u16::max_value() }
+    fn wrapping_add(self, other: u16) -> u16 { u16::wrapping_add(self, other) }
+    fn wrapping_sub(self, other: u16) -> u16 { u16::wrapping_sub(self, other) }
+    fn from_byte(byte: u8) -> u16 { byte as u16 }
+    fn to_byte(self) -> u8 { self as u8 }
+}
+
+impl Cell for u32 {
+    fn zero() -> u32 { 0 }
+    fn one() -> u32 { 1 }
+    fn max_value() -> u32 { u32::max_value() }
+    fn wrapping_add(self, other: u32) -> u32 { u32::wrapping_add(self, other) }
+    fn wrapping_sub(self, other: u32) -> u32 { u32::wrapping_sub(self, other) }
+    fn from_byte(byte: u8) -> u32 { byte as u32 }
+    fn to_byte(self) -> u8 { self as u8 }
+}
+
+pub trait Tape<C> {
+    fn go_left(&mut self);
+    fn go_right(&mut self);
+    fn inc(&mut self);
+    fn dec(&mut self);
+    fn read(&self) -> C;
+    fn write(&mut self, cell: C);
+
+    // The head position, as a signed offset from the tape's starting cell. Used by the
+    // debugger to report where execution is and to center a tape dump.
+    fn head(&self) -> i64;
+
+    // Reads the cell at an arbitrary absolute position without moving the head or allocating;
+    // positions outside what's been touched so far read as zero. Used by the debugger to dump a
+    // window of cells around the head without disturbing the tape.
+    fn peek_at(&self, pos: i64) -> C;
+}
+
+pub struct SimpleTape<C> {
+    pos: usize,
+    data: Vec<C>,
+}
+
+impl<C: Cell> SimpleTape<C> {
+    pub fn new(size: usize) -> SimpleTape<C> {
+        SimpleTape { pos: 0, data: repeat(C::zero()).take(size).collect() }
+    }
+}
+
+impl<C: Cell> Tape<C> for SimpleTape<C> {
+    fn go_left(&mut self)  { self.pos -= 1; }
+    fn go_right(&mut self) { self.pos += 1; }
+    fn inc(&mut self) { self.data[self.pos] = self.data[self.pos].wrapping_add(C::one()); }
+    fn dec(&mut self) { self.data[self.pos] = self.data[self.pos].wrapping_sub(C::one()); }
+    fn read(&self) -> C { self.data[self.pos] }
+    fn write(&mut self, cell: C) { self.data[self.pos] = cell; }
+
+    fn head(&self) -> i64 { self.pos as i64 }
+
+    fn peek_at(&self, pos: i64) -> C {
+        if pos >= 0 && (pos as usize) < self.data.len() {
+            self.data[pos as usize]
+        } else {
+            C::zero()
+        }
+    }
+}
+
+pub struct CircularTape<C> {
+    pos: usize,
+    data: Vec<C>,
+}
+
+impl<C: Cell> CircularTape<C> {
+    pub fn new(size: usize) -> CircularTape<C> {
+        CircularTape { pos: 0, data: repeat(C::zero()).take(size).collect() }
+    }
+}
+
+impl<C: Cell> Tape<C> for CircularTape<C> {
+    fn go_left(&mut self)  { self.pos = (self.pos + self.data.len() - 1) % self.data.len(); }
+    fn go_right(&mut self) { self.pos = (self.pos + 1) % self.data.len(); }
+    fn inc(&mut self) { self.data[self.pos] = self.data[self.pos].wrapping_add(C::one()); }
+    fn dec(&mut self) { self.data[self.pos] = self.data[self.pos].wrapping_sub(C::one()); }
+    fn read(&self) -> C { self.data[self.pos] }
+    fn write(&mut self, cell: C) { self.data[self.pos] = cell; }
+
+    fn head(&self) -> i64 { self.pos as i64 }
+
+    fn peek_at(&self, pos: i64) -> C {
+        let len = self.data.len() as i64;
+        let wrapped = ((pos % len) + len) % len;
+        self.data[wrapped as usize]
+    }
+}
+
+// Size of each lazily-allocated chunk, in cells.
+const CHUNK: usize = 4096;
+
+type Chunk<C> = Box<[C; CHUNK]>;
+
+// A tape with no fixed bounds: it grows in both directions as the head moves past what has
+// already been allocated. Cells are backed by fixed-size chunks that are only allocated once
+// something writes into them, so sparsely-used tapes (e.g. a mandelbrot renderer walking
+// millions of cells to the right) stay cheap.
+pub struct SparseTape<C> {
+    pos: i64,
+    // Chunks for non-negative positions: chunks[0] covers [0, CHUNK), chunks[1] covers
+    // [CHUNK, 2*CHUNK), and so on.
+    chunks: Vec<Option<Chunk<C>>>,
+    // Chunks for negative positions, indexed the mirror image of `chunks`: neg_chunks[0]
+    // covers [-CHUNK, 0), neg_chunks[1] covers [-2*CHUNK, -CHUNK), and so on.
+    neg_chunks: Vec<Option<Chunk<C>>>,
+}
+
+impl<C: Cell> SparseTape<C> {
+    pub fn new() -> SparseTape<C> {
+        SparseTape { pos: 0, chunks: Vec::new(), neg_chunks: Vec::new() }
+    }
+
+    // Maps a tape position to the chunk vector to use, the index of the chunk within it, and
+    // the index of the cell within that chunk.
+    fn locate(pos: i64) -> (bool, usize, usize) {
+        if pos >= 0 {
+            (true, (pos / CHUNK as i64) as usize, (pos % CHUNK as i64) as usize)
+        } else {
+            let n = -pos - 1;
+            (false, (n / CHUNK as i64) as usize, (n % CHUNK as i64) as usize)
+        }
+    }
+
+    fn chunks_mut(&mut self, non_negative: bool) -> &mut Vec<Option<Chunk<C>>> {
+        if non_negative { &mut self.chunks } else { &mut self.neg_chunks }
+    }
+
+    fn get_mut_cell(&mut self, pos: i64) -> &mut C {
+        let (non_negative, chunk_idx, inner_idx) = SparseTape::<C>::locate(pos);
+        let chunks = self.chunks_mut(non_negative);
+
+        if chunk_idx >= chunks.len() {
+            let grow_by = chunk_idx + 1 - chunks.len();
+            chunks.extend(repeat(None).take(grow_by));
+        }
+
+        let slot = &mut chunks[chunk_idx];
+        if slot.is_none() {
+            *slot = Some(Box::new([C::zero(); CHUNK]));
+        }
+
+        &mut slot.as_mut().unwrap()[inner_idx]
+    }
+
+    fn get_cell(&self, pos: i64) -> C {
+        let (non_negative, chunk_idx, inner_idx) = SparseTape::<C>::locate(pos);
+        let chunks = if non_negative { &self.chunks } else { &self.neg_chunks };
+
+        match chunks.get(chunk_idx) {
+            Some(&Some(ref chunk)) => chunk[inner_idx],
+            _ => C::zero(),
+        }
+    }
+}
+
+impl<C: Cell> Tape<C> for SparseTape<C> {
+    fn go_left(&mut self)  { self.pos -= 1; }
+    fn go_right(&mut self) { self.pos += 1; }
+
+    fn inc(&mut self) {
+        let pos = self.pos;
+        let cell = self.get_mut_cell(pos);
+        *cell = cell.wrapping_add(C::one());
+    }
+
+    fn dec(&mut self) {
+        let pos = self.pos;
+        let cell = self.get_mut_cell(pos);
+        *cell = cell.wrapping_sub(C::one());
+    }
+
+    fn read(&self) -> C { self.get_cell(self.pos) }
+    fn write(&mut self, cell: C) { let pos = self.pos; *self.get_mut_cell(pos) = cell; }
+
+    fn head(&self) -> i64 { self.pos }
+    fn peek_at(&self, pos: i64) -> C { self.get_cell(pos) }
+}
+
+// What to do when `,` tries to read past the end of the input.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EofPolicy {
+    Unchanged, // leave the current cell alone
+    Zero,      // write a zero cell
+    AllOnes,   // write the cell type's max value (its "-1" under wraparound)
+}
+
+// Knobs that change how a program is executed without changing its parsed/optimized form:
+// which integer type backs each cell (used by the caller to choose the tape's cell type), and
+// what a `,` does once the input runs out.
+pub struct Config {
+    pub cell_width: CellWidth,
+    pub eof_policy: EofPolicy,
+}
+
+impl Config {
+    pub fn new(cell_width: CellWidth, eof_policy: EofPolicy) -> Config {
+        Config { cell_width: cell_width, eof_policy: eof_policy }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CellWidth { U8, U16, U32 }
+
+// Executes a single instruction against `tape`, jumping `*ip` to the matching loop instruction
+// for `LoopStart`/`LoopEnd` (the caller is still responsible for the unconditional `*ip += 1`
+// afterward, exactly as brainfuck's "jump, then advance" loop semantics require). Shared by the
+// straight-through `execute` and by `Debugger::step`, so the two can never drift apart.
+fn step_op<R: Read, W: Write, C: Cell, T: Tape<C>>(op: Op, input: &mut R, output: &mut W, tape: &mut T,
+                                                     config: &Config, ip: &mut usize) -> io::Result<()> {
+    match op {
+        Op::Inc   => tape.inc(),
+        Op::Dec   => tape.dec(),
+        Op::Left  => tape.go_left(),
+        Op::Right => tape.go_right(),
+        Op::Read  => {
+            let mut byte = [0u8; 1];
+            match input.read(&mut byte) {
+                Ok(n) if n > 0 => tape.write(C::from_byte(byte[0])),
+                _ => match config.eof_policy {
+                    EofPolicy::Unchanged => {}, // Do nothing on EOF.
+                    EofPolicy::Zero      => tape.write(C::zero()),
+                    EofPolicy::AllOnes   => tape.write(C::max_value()),
+                },
+            }
+        },
+        Op::Write => { try!(output.write(&[tape.read().to_byte(); 1])); },
+        Op::LoopStart(loop_end) => if tape.read() == C::zero() { *ip = loop_end; },
+        Op::LoopEnd(loop_start) => if tape.read() != C::zero() { *ip = loop_start; },
+
+        Op::Add(delta) => {
+            if delta >= 0 {
+                for _ in 0..delta { tape.inc(); }
+            } else {
+                for _ in 0..-delta { tape.dec(); }
+            }
+        },
+        Op::Move(delta) => {
+            if delta >= 0 {
+                for _ in 0..delta { tape.go_right(); }
+            } else {
+                for _ in 0..-delta { tape.go_left(); }
+            }
+        },
+        Op::SetZero => tape.write(C::zero()),
+    }
+
+    Ok(())
+}
+
+pub fn execute<R: Read, W: Write, C: Cell, T: Tape<C>>(program: Vec<Op>, input: &mut R, output: &mut W,
+                                                          tape: &mut T, config: &Config) -> io::Result<()> {
+    let mut ip: usize = 0; // Instruction pointer.
+
+    while ip < program.len() {
+        try!(step_op(program[ip], input, output, tape, config, &mut ip));
+        ip += 1;
+    }
+
+    Ok(())
+}
+
+// A snapshot of machine state taken right after a `Debugger::step`.
+pub struct StepInfo<C> {
+    pub ip: usize,
+    pub head: i64,
+    pub cell: C,
+}
+
+// What happened during a single `Debugger::step`.
+pub enum StepResult<C> {
+    Stepped(StepInfo<C>),    // one instruction ran; execution can continue
+    HitBreakpoint(StepInfo<C>), // one instruction ran and the next one sits on a breakpoint
+    Halted,                  // the program counter ran off the end of the program
+}
+
+// Single-steps a `Program` instead of running it to completion, so a caller can inspect machine
+// state between instructions, set breakpoints keyed on source character index, and dump a
+// window of tape cells around the head.
+pub struct Debugger<'p, R, W, C, T> {
+    program: &'p Program,
+    ip: usize,
+    input: R,
+    output: W,
+    tape: T,
+    config: Config,
+    breakpoints: Vec<usize>, // source character indices
+    _cell: PhantomData<C>,
+}
+
+impl<'p, R: Read, W: Write, C: Cell, T: Tape<C>> Debugger<'p, R, W, C, T> {
+    pub fn new(program: &'p Program, input: R, output: W, tape: T, config: Config) -> Debugger<'p, R, W, C, T> {
+        Debugger {
+            program: program,
+            ip: 0,
+            input: input,
+            output: output,
+            tape: tape,
+            config: config,
+            breakpoints: Vec::new(),
+            _cell: PhantomData,
+        }
+    }
+
+    // Sets a breakpoint at the given source character index; `step` reports `HitBreakpoint`
+    // once the instruction it produced is about to run.
+    pub fn add_breakpoint(&mut self, src_index: usize) {
+        if !self.breakpoints.contains(&src_index) {
+            self.breakpoints.push(src_index);
+        }
+    }
+
+    pub fn remove_breakpoint(&mut self, src_index: usize) {
+        self.breakpoints.retain(|&b| b != src_index);
+    }
+
+    // Advances execution by exactly one (already-fused) instruction.
+    pub fn step(&mut self) -> io::Result<StepResult<C>> {
+        if self.ip >= self.program.ops.len() {
+            return Ok(StepResult::Halted);
+        }
+
+        let op = self.program.ops[self.ip];
+        try!(step_op(op, &mut self.input, &mut self.output, &mut self.tape, &self.config, &mut self.ip));
+        self.ip += 1;
+
+        let info = StepInfo { ip: self.ip, head: self.tape.head(), cell: self.tape.read() };
+
+        let at_breakpoint = self.ip < self.program.ops.len()
+            && self.breakpoints.contains(&self.program.src[self.ip]);
+
+        if at_breakpoint {
+            Ok(StepResult::HitBreakpoint(info))
+        } else {
+            Ok(StepResult::Stepped(info))
+        }
+    }
+
+    // Runs until the program halts or the next instruction sits on a breakpoint.
+    pub fn run(&mut self) -> io::Result<StepResult<C>> {
+        loop {
+            match try!(self.step()) {
+                StepResult::Stepped(_) => {},
+                result => return Ok(result),
+            }
+        }
+    }
+
+    // Dumps the cells within `radius` of the head, as `(position, cell)` pairs ordered from
+    // `head - radius` to `head + radius`, without moving the head or allocating new chunks.
+    pub fn dump_tape(&self, radius: i64) -> Vec<(i64, C)> {
+        let head = self.tape.head();
+        (-radius..radius + 1).map(|offset| {
+            let pos = head + offset;
+            (pos, self.tape.peek_at(pos))
+        }).collect()
+    }
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn hello_world() {
+    let program =
+        "++++++++[>++++[>++>+++>+++>+<<<<-]>+>+>->>+[<]<-]>>.>---.+++++++..+++.>>.<-.<.+++.\
+         ------.--------.>>+.>++.";
+    let mut output = Vec::new();
+    let mut tape = SimpleTape::<u8>::new(1024);
+    let config = Config::new(CellWidth::U8, EofPolicy::Unchanged);
+    execute(parse(program).unwrap().ops, &mut io::empty(), &mut output, &mut tape, &config).unwrap();
+    assert_eq!(output.as_slice(), b"Hello World!\n");
+}
+
+#[test]
+fn fuse_runs_folds_consecutive_inc_dec_and_left_right() {
+    let program = optimize(parse("+++--><<").unwrap());
+    assert_eq!(program.ops.len(), 2);
+    match program.ops[0] {
+        Op::Add(1) => {},
+        ref op => panic!("expected Add(1), got {:?}", op),
+    }
+    match program.ops[1] {
+        Op::Move(-1) => {},
+        ref op => panic!("expected Move(-1), got {:?}", op),
+    }
+}
+
+#[test]
+fn fuse_set_zero_recognizes_clear_loop_idiom() {
+    let program = optimize(parse("[-]").unwrap());
+    assert_eq!(program.ops.len(), 1);
+    match program.ops[0] {
+        Op::SetZero => {},
+        ref op => panic!("expected SetZero, got {:?}", op),
+    }
+}
+
+// Regression test for a bug where `fuse_runs` folded a run of `+`/`-` into `Op::Add(i8)`,
+// truncating any run longer than 127 before it was ever applied to the tape. That was invisible
+// for `u8` cells (truncation mod 256 matches wraparound mod 256) but corrupted wider cells, where
+// an unoptimized and an optimized run of the same program disagreed on the final cell value.
+#[test]
+fn long_inc_run_agrees_between_optimized_and_unoptimized_u16_tape() {
+    let program = "+".repeat(200);
+
+    let mut unoptimized_tape = SimpleTape::<u16>::new(4);
+    let config = Config::new(CellWidth::U16, EofPolicy::Unchanged);
+    execute(parse(&program).unwrap().ops, &mut io::empty(), &mut Vec::new(), &mut unoptimized_tape, &config)
+        .unwrap();
+
+    let mut optimized_tape = SimpleTape::<u16>::new(4);
+    let ops = optimize(parse(&program).unwrap()).ops;
+    execute(ops, &mut io::empty(), &mut Vec::new(), &mut optimized_tape, &config).unwrap();
+
+    assert_eq!(unoptimized_tape.read(), 200);
+    assert_eq!(optimized_tape.read(), 200);
+}
+
+#[test]
+fn u8_cell_wraps_instead_of_panicking() {
+    let mut tape = SimpleTape::<u8>::new(1);
+    for _ in 0..256 { tape.inc(); }
+    assert_eq!(tape.read(), 0);
+
+    tape.dec();
+    assert_eq!(tape.read(), 255);
+}
+
+#[test]
+fn sparse_tape_reads_unallocated_cells_as_zero() {
+    let tape = SparseTape::<u8>::new();
+    assert_eq!(tape.peek_at(0), 0);
+    assert_eq!(tape.peek_at(CHUNK as i64), 0);
+    assert_eq!(tape.peek_at(-(CHUNK as i64)), 0);
+}
+
+#[test]
+fn sparse_tape_writes_survive_crossing_a_chunk_boundary_going_right() {
+    let mut tape = SparseTape::<u8>::new();
+    for _ in 0..CHUNK - 1 { tape.go_right(); }
+
+    tape.write(1); // last cell of chunk 0
+    tape.go_right();
+    tape.write(2); // first cell of chunk 1
+
+    assert_eq!(tape.peek_at(CHUNK as i64 - 1), 1);
+    assert_eq!(tape.peek_at(CHUNK as i64), 2);
+}
+
+#[test]
+fn sparse_tape_writes_survive_crossing_a_chunk_boundary_going_left() {
+    let mut tape = SparseTape::<u8>::new();
+    for _ in 0..CHUNK { tape.go_left(); }
+
+    tape.write(1); // last cell of neg_chunks[0]
+    tape.go_left();
+    tape.write(2); // first cell of neg_chunks[1]
+
+    assert_eq!(tape.peek_at(-(CHUNK as i64)), 1);
+    assert_eq!(tape.peek_at(-(CHUNK as i64) - 1), 2);
+}
+
+#[test]
+fn sparse_tape_positive_and_negative_positions_are_independent() {
+    let mut tape = SparseTape::<u8>::new();
+    tape.write(1); // position 0, in chunks[0]
+
+    tape.go_left();
+    tape.write(2); // position -1, in neg_chunks[0]
+
+    assert_eq!(tape.peek_at(0), 1);
+    assert_eq!(tape.peek_at(-1), 2);
+}
+
+#[test]
+fn eof_policy_is_applied_once_input_runs_out() {
+    // Seven `+`s leave a nonzero cell behind, so `Unchanged` leaving it alone is distinguishable
+    // from `Zero`/`AllOnes` overwriting it once the following `,` hits end of input.
+    fn read_after_eof(eof_policy: EofPolicy) -> u8 {
+        let mut tape = SimpleTape::<u8>::new(1);
+        let config = Config::new(CellWidth::U8, eof_policy);
+        execute(parse("+++++++,").unwrap().ops, &mut io::empty(), &mut Vec::new(), &mut tape, &config)
+            .unwrap();
+        tape.read()
+    }
+
+    assert_eq!(read_after_eof(EofPolicy::Unchanged), 7);
+    assert_eq!(read_after_eof(EofPolicy::Zero), 0);
+    assert_eq!(read_after_eof(EofPolicy::AllOnes), 255);
+}
+
+#[cfg(feature = "std")]
+#[test]
+fn debugger_steps_breaks_and_dumps_the_tape() {
+    // "+++." -- three Incs (src 0, 1, 2) then a Write (src 3). Debugged unfused, so the
+    // breakpoint on src 2 (the third Inc) is reachable even though it isn't the start of the run
+    // fuse_runs would otherwise fold it into.
+    let program = parse("+++.").unwrap();
+    let config = Config::new(CellWidth::U8, EofPolicy::Unchanged);
+    let mut debugger = Debugger::new(&program, io::empty(), Vec::new(), SimpleTape::<u8>::new(8), config);
+    debugger.add_breakpoint(2);
+
+    match debugger.run().unwrap() {
+        StepResult::HitBreakpoint(info) => {
+            assert_eq!(info.ip, 2);
+            assert_eq!(info.cell, 2); // two Incs have run; the third is about to
+        },
+        _ => panic!("expected HitBreakpoint, got a different StepResult"),
+    }
+
+    let dump = debugger.dump_tape(1);
+    assert_eq!(dump, vec![(-1, 0), (0, 2), (1, 0)]);
+
+    match debugger.run().unwrap() {
+        StepResult::Halted => {},
+        _ => panic!("expected the program to run to completion after the breakpoint"),
+    }
+}
+
+#[test]
+fn disassemble_renders_fused_ops_with_their_source_index() {
+    let lines = disassemble(&optimize(parse("+++.").unwrap()));
+    assert_eq!(lines, vec!["Add(3) @ src:0", "Write @ src:3"]);
+}
@@ -1,164 +1,307 @@
+//! CLI front end. Everything here needs `std` (file I/O, `stdin`/`stdout`, process args), so it
+//! stays behind the `std` feature and out of the `no_std`-capable library in `lib.rs`.
+#![cfg(feature = "std")]
+
+extern crate brainrust;
+
 use std::env;
 use std::fs::File;
-use std::io::{self, Read, Write, stdin, stdout};
-use std::iter::repeat;
+use std::io::{self, stdin, stdout};
 use std::path::Path;
+use std::process;
 
-// A brainfuck instruction.
-enum Op {
-    Inc,   // +
-    Dec,   // -
-    Left,  // <
-    Right, // >
-    Read,  // ,
-    Write, // .
-
-    // Each loop instruction stores the index of its matching loop instruction.
-    LoopStart(usize), // [
-    LoopEnd(usize),   // ]
+use brainrust::{Cell, CellWidth, CircularTape, Config, Debugger, EofPolicy, Program, SimpleTape,
+                 SparseTape, StepResult, Tape, disassemble, execute, optimize, parse};
+
+fn read_file(name: &str) -> io::Result<String> {
+    File::open(&Path::new(name)).and_then(|mut file| {
+        use std::io::Read;
+        let mut s = String::new();
+        try!(file.read_to_string(&mut s));
+        Ok(s)
+    })
 }
 
-// Parse errors contain the index of the offending character in the original program source.
-#[derive(Clone, Debug, Eq, PartialEq)]
-enum ParseError { UnmatchedLoopStart(usize), UnmatchedLoopEnd(usize) }
-
-fn parse(program: &str) -> Result<Vec<Op>, ParseError> {
-    let mut ops = Vec::new();
-    let mut loop_stack = Vec::new();
-
-    for (i, op) in program.chars().enumerate() {
-        match op {
-            '+' => ops.push(Op::Inc),
-            '-' => ops.push(Op::Dec),
-            '<' => ops.push(Op::Left),
-            '>' => ops.push(Op::Right),
-            ',' => ops.push(Op::Read),
-            '.' => ops.push(Op::Write),
-            '[' => {
-                loop_stack.push(i);
-                ops.push(Op::LoopStart(0));
-            },
-            ']' => match loop_stack.pop() {
-                Some(loop_start) => ops.push(Op::LoopEnd(loop_start)),
-                None             => return Err(ParseError::UnmatchedLoopEnd(i)),
+// Which `Tape` implementation to back execution with; chosen on the command line via `--tape`.
+// `Simple`/`Circular` both need an upfront size, since neither grows; `Sparse` never does.
+#[cfg_attr(test, derive(Debug, PartialEq, Eq))]
+enum TapeKind {
+    Simple(usize),
+    Circular(usize),
+    Sparse,
+}
+
+struct Options {
+    path: String,
+    config: Config,
+    tape_kind: TapeKind,
+    debug: bool,
+    disassemble: bool,
+    breakpoints: Vec<usize>,
+}
+
+fn usage(program_name: &str) -> String {
+    format!(
+        "usage: {} [options] <file>\n\
+         \n\
+         options:\n\
+         \x20 --cell-width <u8|u16|u32>        bits per tape cell (default: u8)\n\
+         \x20 --eof <unchanged|zero|allones>    what `,` does at end of input (default: unchanged)\n\
+         \x20 --tape <simple|circular|sparse>   tape implementation (default: simple)\n\
+         \x20 --tape-size <n>                   cells in a simple/circular tape (default: 1024)\n\
+         \x20 --debug                           step through the program with the debugger,\n\
+         \x20                                   printing a tape dump at every breakpoint hit\n\
+         \x20 --breakpoint <src-index>          break at this source character index (repeatable);\n\
+         \x20                                   only takes effect with --debug\n\
+         \x20 --disassemble                     print the fused IR (the unfused IR with --debug)\n\
+         \x20                                   instead of running the program",
+        program_name,
+    )
+}
+
+// Parses the flags described by `usage` out of the process arguments, defaulting to a 1024-cell
+// `SimpleTape<u8>` with EOF left alone.
+fn parse_args(args: &[String]) -> Result<Options, String> {
+    let mut cell_width = CellWidth::U8;
+    let mut eof_policy = EofPolicy::Unchanged;
+    let mut tape_kind_name = "simple".to_string();
+    let mut tape_size: usize = 1024;
+    let mut debug = false;
+    let mut disassemble = false;
+    let mut breakpoints = Vec::new();
+    let mut path = None;
+
+    let mut i = 0;
+    while i < args.len() {
+        let arg = &args[i];
+
+        match arg.as_str() {
+            "--debug"       => debug = true,
+            "--disassemble" => disassemble = true,
+            "--cell-width" | "--eof" | "--tape" | "--tape-size" | "--breakpoint" => {
+                i += 1;
+                let value = match args.get(i) {
+                    Some(value) => value.clone(),
+                    None => return Err(format!("{} requires a value", arg)),
+                };
+
+                match arg.as_str() {
+                    "--cell-width" => cell_width = match value.as_str() {
+                        "u8"  => CellWidth::U8,
+                        "u16" => CellWidth::U16,
+                        "u32" => CellWidth::U32,
+                        _ => return Err(format!("unknown cell width: {}", value)),
+                    },
+                    "--eof" => eof_policy = match value.as_str() {
+                        "unchanged" => EofPolicy::Unchanged,
+                        "zero"      => EofPolicy::Zero,
+                        "allones"   => EofPolicy::AllOnes,
+                        _ => return Err(format!("unknown EOF policy: {}", value)),
+                    },
+                    "--tape" => tape_kind_name = value,
+                    "--tape-size" => tape_size = match value.parse() {
+                        Ok(n) => n,
+                        Err(_) => return Err(format!("invalid tape size: {}", value)),
+                    },
+                    "--breakpoint" => breakpoints.push(match value.parse() {
+                        Ok(n) => n,
+                        Err(_) => return Err(format!("invalid breakpoint source index: {}", value)),
+                    }),
+                    _ => unreachable!(),
+                }
             },
-            _   => {}
+            _ if path.is_none() => path = Some(arg.clone()),
+            _ => return Err(format!("unexpected argument: {}", arg)),
         }
+
+        i += 1;
     }
 
-    if loop_stack.is_empty() {
-        Ok(ops)
-    } else {
-        Err(ParseError::UnmatchedLoopStart(loop_stack[0]))
+    let tape_kind = match tape_kind_name.as_str() {
+        "simple"   => TapeKind::Simple(tape_size),
+        "circular" => TapeKind::Circular(tape_size),
+        "sparse"   => TapeKind::Sparse,
+        _ => return Err(format!("unknown tape kind: {}", tape_kind_name)),
+    };
+
+    match path {
+        Some(path) => Ok(Options {
+            path: path,
+            config: Config::new(cell_width, eof_policy),
+            tape_kind: tape_kind,
+            debug: debug,
+            disassemble: disassemble,
+            breakpoints: breakpoints,
+        }),
+        None => Err("missing <file> argument".to_string()),
     }
 }
 
-trait Tape {
-    fn go_left(&mut self);
-    fn go_right(&mut self);
-    fn inc(&mut self);
-    fn dec(&mut self);
-    fn read(&self) -> u8;
-    fn write(&mut self, byte: u8);
-}
+fn main() {
+    let args: Vec<String> = env::args().collect();
+    let opts = match parse_args(&args[1..]) {
+        Ok(opts) => opts,
+        Err(err) => {
+            println!("error: {}", err);
+            println!("{}", usage(&args[0]));
+            process::exit(1);
+        },
+    };
 
-struct SimpleTape {
-    pos: usize,
-    data: Vec<u8>,
-}
+    let source = read_file(&opts.path).unwrap();
+    let program = parse(&source).unwrap();
 
-impl SimpleTape {
-    fn new(size: usize) -> SimpleTape {
-        SimpleTape { pos: 0, data: repeat(0u8).take(size).collect() }
+    if opts.disassemble {
+        let program = if opts.debug { program } else { optimize(program) };
+        for line in disassemble(&program) { println!("{}", line); }
+        return;
     }
-}
 
-impl Tape for SimpleTape {
-    fn go_left(&mut self)  { self.pos -= 1; }
-    fn go_right(&mut self) { self.pos += 1; }
-    fn inc(&mut self) { self.data[self.pos] += 1; }
-    fn dec(&mut self) { self.data[self.pos] -= 1; }
-    fn read(&self) -> u8 { self.data[self.pos] }
-    fn write(&mut self, byte: u8) { self.data[self.pos] = byte; }
+    if opts.debug {
+        // Debug against the unfused `program` rather than `optimize(program)`: fusion only keeps
+        // the source index of the *first* character in each folded run (see `fuse_runs` and
+        // `fuse_set_zero`), so a breakpoint on any other character of a run could never fire
+        // against the optimized IR. Debugging trades the optimizer's speedup for breakpoints
+        // that work on every character; a plain (non-debug) run still gets the fused, faster IR.
+        debug(&program, opts.config, &opts.tape_kind, &opts.breakpoints).unwrap();
+    } else {
+        run(optimize(program).ops, &opts.config, &opts.tape_kind).unwrap();
+    }
 }
 
-struct CircularTape {
-    pos: usize,
-    data: Vec<u8>,
+// Dispatches to the cell-width/tape-kind monomorphization of `execute` selected by `config` and
+// `tape_kind`. Rust generics are resolved at compile time, but both knobs are only known at
+// runtime, so every combination gets its own arm here.
+fn run(program: Vec<brainrust::Op>, config: &Config, tape_kind: &TapeKind) -> io::Result<()> {
+    match *tape_kind {
+        TapeKind::Simple(size) => match config.cell_width {
+            CellWidth::U8  =>
+                execute(program, &mut stdin(), &mut stdout(), &mut SimpleTape::<u8>::new(size), config),
+            CellWidth::U16 =>
+                execute(program, &mut stdin(), &mut stdout(), &mut SimpleTape::<u16>::new(size), config),
+            CellWidth::U32 =>
+                execute(program, &mut stdin(), &mut stdout(), &mut SimpleTape::<u32>::new(size), config),
+        },
+        TapeKind::Circular(size) => match config.cell_width {
+            CellWidth::U8  =>
+                execute(program, &mut stdin(), &mut stdout(), &mut CircularTape::<u8>::new(size), config),
+            CellWidth::U16 =>
+                execute(program, &mut stdin(), &mut stdout(), &mut CircularTape::<u16>::new(size), config),
+            CellWidth::U32 =>
+                execute(program, &mut stdin(), &mut stdout(), &mut CircularTape::<u32>::new(size), config),
+        },
+        TapeKind::Sparse => match config.cell_width {
+            CellWidth::U8  =>
+                execute(program, &mut stdin(), &mut stdout(), &mut SparseTape::<u8>::new(), config),
+            CellWidth::U16 =>
+                execute(program, &mut stdin(), &mut stdout(), &mut SparseTape::<u16>::new(), config),
+            CellWidth::U32 =>
+                execute(program, &mut stdin(), &mut stdout(), &mut SparseTape::<u32>::new(), config),
+        },
+    }
 }
 
-impl CircularTape {
-    fn new(size: usize) -> CircularTape {
-        CircularTape { pos: 0, data: repeat(0u8).take(size).collect() }
+// Same dispatch as `run`, but drives `program` through a `Debugger` instead of `execute`: runs to
+// completion, printing the machine state and a small tape dump every time a breakpoint is hit.
+fn debug(program: &Program, config: Config, tape_kind: &TapeKind, breakpoints: &[usize]) -> io::Result<()> {
+    match *tape_kind {
+        TapeKind::Simple(size) => match config.cell_width {
+            CellWidth::U8  => debug_run(program, SimpleTape::<u8>::new(size), config, breakpoints),
+            CellWidth::U16 => debug_run(program, SimpleTape::<u16>::new(size), config, breakpoints),
+            CellWidth::U32 => debug_run(program, SimpleTape::<u32>::new(size), config, breakpoints),
+        },
+        TapeKind::Circular(size) => match config.cell_width {
+            CellWidth::U8  => debug_run(program, CircularTape::<u8>::new(size), config, breakpoints),
+            CellWidth::U16 => debug_run(program, CircularTape::<u16>::new(size), config, breakpoints),
+            CellWidth::U32 => debug_run(program, CircularTape::<u32>::new(size), config, breakpoints),
+        },
+        TapeKind::Sparse => match config.cell_width {
+            CellWidth::U8  => debug_run(program, SparseTape::<u8>::new(), config, breakpoints),
+            CellWidth::U16 => debug_run(program, SparseTape::<u16>::new(), config, breakpoints),
+            CellWidth::U32 => debug_run(program, SparseTape::<u32>::new(), config, breakpoints),
+        },
     }
 }
 
-impl Tape for CircularTape {
-    fn go_left(&mut self)  { self.pos = (self.pos - 1) % self.data.len(); }
-    fn go_right(&mut self) { self.pos = (self.pos + 1) % self.data.len(); }
-    fn inc(&mut self) { self.data[self.pos] += 1; }
-    fn dec(&mut self) { self.data[self.pos] -= 1; }
-    fn read(&self) -> u8 { self.data[self.pos] }
-    fn write(&mut self, byte: u8) { self.data[self.pos] = byte; }
-}
+fn debug_run<C: Cell + ::std::fmt::Debug, T: Tape<C>>(program: &Program, tape: T, config: Config,
+                                                         breakpoints: &[usize]) -> io::Result<()> {
+    let mut debugger = Debugger::new(program, stdin(), stdout(), tape, config);
+    for &bp in breakpoints { debugger.add_breakpoint(bp); }
 
-fn execute<R: Read, W: Write, T: Tape>(program: Vec<Op>, input: &mut R, output: &mut W,
-                                          tape: &mut T) -> io::Result<()> {
-    let mut ip: usize = 0; // Instruction pointer.
-
-    while ip < program.len() {
-        match program[ip] {
-            Op::Inc   => tape.inc(),
-            Op::Dec   => tape.dec(),
-            Op::Left  => tape.go_left(),
-            Op::Right => tape.go_right(),
-            Op::Read  => {
-                let mut byte = [0u8; 1];
-                match input.read(&mut byte) {
-                    Ok(_)  => tape.write(byte[0]),
-                    Err(_) => {} // Do nothing on EOF.
+    loop {
+        match try!(debugger.run()) {
+            StepResult::Halted => return Ok(()),
+            StepResult::HitBreakpoint(info) => {
+                println!("--- breakpoint: ip={} head={} cell={:?} ---", info.ip, info.head, info.cell);
+                for (pos, cell) in debugger.dump_tape(4) {
+                    println!("  {}{}: {:?}", if pos == info.head { "-> " } else { "   " }, pos, cell);
                 }
             },
-            Op::Write => { try!(output.write(&[tape.read(); 1])); },
-            Op::LoopStart(loop_end) => if tape.read() == 0 { ip = loop_end; },
-            Op::LoopEnd(loop_start) => if tape.read() != 0 { ip = loop_start; },
+            StepResult::Stepped(_) => unreachable!("Debugger::run only returns on Halted or HitBreakpoint"),
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-        ip += 1;
+    fn args(strs: &[&str]) -> Vec<String> {
+        strs.iter().map(|s| s.to_string()).collect()
     }
 
-    Ok(())
-}
+    #[test]
+    fn defaults_to_simple_u8_tape_with_eof_unchanged() {
+        let opts = parse_args(&args(&["file.bf"])).unwrap();
+        assert_eq!(opts.path, "file.bf");
+        assert_eq!(opts.config.cell_width, CellWidth::U8);
+        assert_eq!(opts.config.eof_policy, EofPolicy::Unchanged);
+        assert_eq!(opts.tape_kind, TapeKind::Simple(1024));
+    }
 
-fn read_file(name: &str) -> io::Result<String> {
-    File::open(&Path::new(name)).and_then(|mut file| {
-        let mut s = String::new();
-        try!(file.read_to_string(&mut s));
-        Ok(s)
-    })
-}
+    #[test]
+    fn parses_cell_width() {
+        let opts = parse_args(&args(&["--cell-width", "u16", "file.bf"])).unwrap();
+        assert_eq!(opts.config.cell_width, CellWidth::U16);
 
-fn main() {
-    let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        println!("usage: {} <file>", args[0]);
-        return;
+        let opts = parse_args(&args(&["--cell-width", "u32", "file.bf"])).unwrap();
+        assert_eq!(opts.config.cell_width, CellWidth::U32);
     }
 
-    let mut tape = SimpleTape::new(1024);
+    #[test]
+    fn parses_eof_policy() {
+        let opts = parse_args(&args(&["--eof", "zero", "file.bf"])).unwrap();
+        assert_eq!(opts.config.eof_policy, EofPolicy::Zero);
 
-    read_file(&args[1]).and_then(|program| {
-        execute(parse(&program).unwrap(), &mut stdin(), &mut stdout(), &mut tape)
-    }).unwrap();
-}
+        let opts = parse_args(&args(&["--eof", "allones", "file.bf"])).unwrap();
+        assert_eq!(opts.config.eof_policy, EofPolicy::AllOnes);
+    }
 
-#[test]
-fn hello_world() {
-    use std::io::util::NullReader;
+    #[test]
+    fn parses_tape_kind_and_size() {
+        let opts = parse_args(&args(&["--tape", "circular", "--tape-size", "42", "file.bf"])).unwrap();
+        assert_eq!(opts.tape_kind, TapeKind::Circular(42));
 
-    let program = include_str!("../hello_world.bf");
-    let mut output = Vec::new();
-    let mut tape = SimpleTape::new(1024);
-    execute(parse(program).unwrap(), &mut NullReader, &mut output, &mut tape).unwrap();
-    assert_eq!(output.as_slice(), b"Hello World!\n");
+        let opts = parse_args(&args(&["--tape", "sparse", "file.bf"])).unwrap();
+        assert_eq!(opts.tape_kind, TapeKind::Sparse);
+    }
+
+    #[test]
+    fn rejects_unknown_flag_values() {
+        assert!(parse_args(&args(&["--cell-width", "u24", "file.bf"])).is_err());
+        assert!(parse_args(&args(&["--eof", "wat", "file.bf"])).is_err());
+        assert!(parse_args(&args(&["--tape", "infinite", "file.bf"])).is_err());
+        assert!(parse_args(&args(&["--tape-size", "not-a-number", "file.bf"])).is_err());
+    }
+
+    #[test]
+    fn rejects_a_flag_missing_its_value() {
+        assert!(parse_args(&args(&["--eof"])).is_err());
+    }
+
+    #[test]
+    fn rejects_missing_or_extra_positional_arguments() {
+        assert!(parse_args(&args(&[])).is_err());
+        assert!(parse_args(&args(&["one.bf", "two.bf"])).is_err());
+    }
 }